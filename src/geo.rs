@@ -0,0 +1,196 @@
+//! Conversions between this crate's primitives and common geospatial
+//! interchange formats, so map data can be imported from (or exported to)
+//! the wider geo ecosystem instead of being hand-built.
+
+#[cfg(feature = "geojson")]
+use geojson::{Feature, Geometry, JsonObject, Value};
+
+use crate::{Coord, Road};
+#[cfg(feature = "geojson")]
+use crate::{BusStop, Segment, TrainStation};
+
+#[cfg(feature = "geojson")]
+impl Road {
+    /// Convert this road to a GeoJSON `Feature` wrapping a `LineString`,
+    /// with `name`, `forbidden_to_pedestrians`, `forbidden_to_bikes` and the
+    /// first segment's `layer` carried over as properties.
+    pub fn to_geojson(&self) -> Feature {
+        let mut coordinates: Vec<Vec<f64>> = Vec::new();
+        if let Some(first) = self.segments.first() {
+            coordinates.push(vec![first.a.lon, first.a.lat]);
+        }
+        for segment in &self.segments {
+            coordinates.push(vec![segment.b.lon, segment.b.lat]);
+        }
+
+        let mut properties = JsonObject::new();
+        if let Some(name) = &self.name {
+            properties.insert("name".to_string(), name.clone().into());
+        }
+        properties.insert("forbidden_to_pedestrians".to_string(), self.forbidden_to_pedestrians.into());
+        properties.insert("forbidden_to_bikes".to_string(), self.forbidden_to_bikes.into());
+        if let Some(layer) = self.segments.first().and_then(|s| s.layer) {
+            properties.insert("layer".to_string(), layer.into());
+        }
+
+        Feature {
+            bbox: None,
+            geometry: Some(Geometry::new(Value::LineString(coordinates))),
+            id: None,
+            properties: Some(properties),
+            foreign_members: None,
+        }
+    }
+
+    /// Build a `Road` from a GeoJSON `Feature` wrapping a `LineString`.
+    /// Returns `None` if the feature isn't a `LineString` with at least two
+    /// points.
+    pub fn from_geojson(feature: &Feature) -> Option<Road> {
+        let geometry = feature.geometry.as_ref()?;
+        let coordinates = match &geometry.value {
+            Value::LineString(coordinates) => coordinates,
+            _ => return None,
+        };
+        if coordinates.len() < 2 {
+            return None;
+        }
+
+        let name = feature.property("name").and_then(|v| v.as_str()).map(|s| s.to_string());
+        let forbidden_to_pedestrians = feature.property("forbidden_to_pedestrians").and_then(|v| v.as_bool()).unwrap_or(false);
+        let forbidden_to_bikes = feature.property("forbidden_to_bikes").and_then(|v| v.as_bool()).unwrap_or(false);
+        let layer = feature.property("layer").and_then(|v| v.as_i64()).map(|v| v as i8);
+
+        let points: Vec<Coord> = coordinates.iter().map(|c| Coord{lat: c[1], lon: c[0]}).collect();
+        let segments = points.windows(2).map(|w| Segment{a: w[0], b: w[1], layer}).collect();
+
+        Some(Road{segments, name, forbidden_to_pedestrians, forbidden_to_bikes})
+    }
+}
+
+#[cfg(feature = "geojson")]
+impl Coord {
+    /// Convert this point to a GeoJSON `Feature` wrapping a `Point`.
+    pub fn to_geojson(&self) -> Feature {
+        Feature {
+            bbox: None,
+            geometry: Some(Geometry::new(Value::Point(vec![self.lon, self.lat]))),
+            id: None,
+            properties: None,
+            foreign_members: None,
+        }
+    }
+
+    /// Build a `Coord` from a GeoJSON `Feature` wrapping a `Point`.
+    pub fn from_geojson(feature: &Feature) -> Option<Coord> {
+        match &feature.geometry.as_ref()?.value {
+            Value::Point(c) => Some(Coord{lat: c[1], lon: c[0]}),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "geojson")]
+impl BusStop {
+    /// Convert this stop to a GeoJSON `Feature` wrapping a `Point`, with
+    /// `id` and `name` carried over as properties.
+    pub fn to_geojson(&self) -> Feature {
+        let mut properties = JsonObject::new();
+        properties.insert("id".to_string(), self.id.clone().into());
+        properties.insert("name".to_string(), self.name.clone().into());
+        Feature {
+            bbox: None,
+            geometry: Some(Geometry::new(Value::Point(vec![self.position.lon, self.position.lat]))),
+            id: None,
+            properties: Some(properties),
+            foreign_members: None,
+        }
+    }
+
+    /// Build a `BusStop` from a GeoJSON `Feature` wrapping a `Point`.
+    pub fn from_geojson(feature: &Feature) -> Option<BusStop> {
+        let position = Coord::from_geojson(feature)?;
+        let id = feature.property("id").and_then(|v| v.as_str())?.to_string();
+        let name = feature.property("name").and_then(|v| v.as_str())?.to_string();
+        Some(BusStop{position, id, name})
+    }
+}
+
+#[cfg(feature = "geojson")]
+impl TrainStation {
+    /// Convert this station to a GeoJSON `Feature` wrapping a `Point`, with
+    /// `id`, `name` and `region_id` carried over as properties.
+    pub fn to_geojson(&self) -> Feature {
+        let mut properties = JsonObject::new();
+        properties.insert("id".to_string(), self.id.clone().into());
+        properties.insert("name".to_string(), self.name.clone().into());
+        properties.insert("region_id".to_string(), self.region_id.into());
+        Feature {
+            bbox: None,
+            geometry: Some(Geometry::new(Value::Point(vec![self.position.lon, self.position.lat]))),
+            id: None,
+            properties: Some(properties),
+            foreign_members: None,
+        }
+    }
+
+    /// Build a `TrainStation` from a GeoJSON `Feature` wrapping a `Point`.
+    pub fn from_geojson(feature: &Feature) -> Option<TrainStation> {
+        let position = Coord::from_geojson(feature)?;
+        let id = feature.property("id").and_then(|v| v.as_str())?.to_string();
+        let name = feature.property("name").and_then(|v| v.as_str())?.to_string();
+        let region_id = feature.property("region_id").and_then(|v| v.as_u64())? as u8;
+        Some(TrainStation{name, id, region_id, position})
+    }
+}
+
+/// Writer for the WKT (Well-Known Text) geometry format. Unlike the GeoJSON
+/// conversions above, this has no external dependency and is always
+/// available.
+pub trait ToWkt {
+    fn to_wkt(&self) -> String;
+}
+
+impl ToWkt for Coord {
+    fn to_wkt(&self) -> String {
+        format!("POINT({} {})", self.lon, self.lat)
+    }
+}
+
+impl ToWkt for Road {
+    fn to_wkt(&self) -> String {
+        let mut points: Vec<String> = Vec::new();
+        if let Some(first) = self.segments.first() {
+            points.push(format!("{} {}", first.a.lon, first.a.lat));
+        }
+        for segment in &self.segments {
+            points.push(format!("{} {}", segment.b.lon, segment.b.lat));
+        }
+        format!("LINESTRING({})", points.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Segment;
+
+    #[test]
+    fn coord_to_wkt_test() {
+        let coord = Coord{lat: 2.0, lon: 1.0};
+        assert_eq!(coord.to_wkt(), "POINT(1 2)");
+    }
+
+    #[test]
+    fn road_to_wkt_test() {
+        let road = Road{
+            name: None,
+            segments: vec![
+                Segment{a: Coord{lat:1.0, lon: 1.0}, b: Coord{lat: 2.0, lon: 2.0}, layer: None},
+                Segment{a: Coord{lat:2.0, lon: 2.0}, b: Coord{lat: 3.0, lon: 3.0}, layer: None},
+            ],
+            forbidden_to_pedestrians: false,
+            forbidden_to_bikes: false,
+        };
+        assert_eq!(road.to_wkt(), "LINESTRING(1 1, 2 2, 3 3)");
+    }
+}
@@ -1,6 +1,16 @@
+mod network;
+pub use network::RoadNetwork;
+mod geo;
+pub use geo::ToWkt;
+mod routing;
+pub use routing::{Graph, TravelMode};
+mod polygon;
+pub use polygon::Polygon;
+
 const TOLERANCE: f64 = 1e-9;
 static SPATIAL_TOLERANCE: f64 = 0.5e-3; // 50 cm
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Copy,Clone,Debug)]
 pub struct Coord{
     pub lat: f64,
@@ -30,6 +40,12 @@ impl std::ops::Mul<f64> for Coord {
         Coord{lat:self.lat*m,lon:self.lon*m}
     }
 }
+impl Eq for Coord {}
+impl std::cmp::PartialOrd for Coord {
+    fn partial_cmp(&self, other: &Coord) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
 impl std::cmp::Ord for Coord {
     fn cmp(&self, other: &Coord) -> std::cmp::Ordering {
         if self.lon==other.lon {
@@ -43,6 +59,7 @@ impl std::cmp::Ord for Coord {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Direction {
     Forward,
     Backward
@@ -76,12 +93,80 @@ impl Coord{
     }
 }
 
+/// A point on the unit sphere, used internally to do great-circle geometry
+/// without the small-angle error that flat lat/lon arithmetic introduces.
+#[derive(Copy, Clone, Debug)]
+struct Vec3 {
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+impl Vec3 {
+    fn from_coord(c: &Coord) -> Vec3 {
+        let phi = c.lat.to_radians();
+        let lambda = c.lon.to_radians();
+        Vec3 {
+            x: phi.cos() * lambda.cos(),
+            y: phi.cos() * lambda.sin(),
+            z: phi.sin(),
+        }
+    }
+
+    fn to_coord(self) -> Coord {
+        Coord {
+            lat: self.z.atan2((self.x * self.x + self.y * self.y).sqrt()).to_degrees(),
+            lon: self.y.atan2(self.x).to_degrees(),
+        }
+    }
+
+    fn cross(&self, o: Vec3) -> Vec3 {
+        Vec3 {
+            x: self.y * o.z - self.z * o.y,
+            y: self.z * o.x - self.x * o.z,
+            z: self.x * o.y - self.y * o.x,
+        }
+    }
+
+    fn dot(&self, o: Vec3) -> f64 {
+        self.x * o.x + self.y * o.y + self.z * o.z
+    }
+
+    fn sub(&self, o: Vec3) -> Vec3 {
+        Vec3 { x: self.x - o.x, y: self.y - o.y, z: self.z - o.z }
+    }
+
+    fn add(&self, o: Vec3) -> Vec3 {
+        Vec3 { x: self.x + o.x, y: self.y + o.y, z: self.z + o.z }
+    }
+
+    fn scale(&self, s: f64) -> Vec3 {
+        Vec3 { x: self.x * s, y: self.y * s, z: self.z * s }
+    }
+
+    fn norm(&self) -> f64 {
+        self.dot(*self).sqrt()
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Segment {
     pub a: Coord,
     pub b: Coord,
     pub layer: Option<i8>,
 }
 
+/// The result of intersecting two segments: they may not meet at all, meet
+/// at a single point, or - when collinear - share a whole sub-segment.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub enum SegmentIntersection {
+    None,
+    Point(Coord),
+    Overlap(Segment),
+}
+
 impl Segment {
     pub fn into_tuple(&self) -> ((f64, f64), (f64, f64)){
         ((self.a.lat, self.a.lon), ((self.b.lat, self.b.lon)))
@@ -98,12 +183,12 @@ impl Segment {
         }
     }
 
-    /// Check if two segments intersect
-    pub fn intersection(&self, other: &Segment) -> Option<Coord> {
-        if let Some(_) = self.layer{
-            if !self.is_contiguous(other) && self.layer !=other.layer{
-                return None;
-            }
+    /// Check if two segments intersect. Distinguishes a single touch point
+    /// from the case where the segments are collinear and share a whole
+    /// sub-segment, instead of producing `NaN`/`inf` coordinates.
+    pub fn intersection(&self, other: &Segment) -> SegmentIntersection {
+        if self.layer.is_some() && !self.is_contiguous(other) && self.layer != other.layer {
+            return SegmentIntersection::None;
         }
         let p1 = self.a;
         let p2 = self.b;
@@ -119,15 +204,103 @@ impl Segment {
 
         let over: f64 = ap*bq-aq*bp;
 
+        if over.abs() < TOLERANCE {
+            let dir = p2-p1;
+            let to_other_a = q1-p1;
+            if (dir.lon*to_other_a.lat - dir.lat*to_other_a.lon).abs() < TOLERANCE {
+                return self.collinear_overlap(other);
+            }
+            return SegmentIntersection::None;
+        }
+
         let ans = Coord{lat:(bp*cq-bq*cp)/over, lon:(aq*cp-ap*cq)/over};
 
         if self.contains(&ans) && other.contains(&ans) {
-            Some(ans)
+            SegmentIntersection::Point(ans)
+        } else {
+            SegmentIntersection::None
+        }
+    }
+
+    /// Compute the overlap between `self` and `other`, assuming they have
+    /// already been found to lie on the same line. Returns `Point` when they
+    /// only touch at one end, `Overlap` when they share a sub-segment, and
+    /// `None` when they don't actually reach each other.
+    fn collinear_overlap(&self, other: &Segment) -> SegmentIntersection {
+        let dir = self.b-self.a;
+        let len = dir.norm();
+        if len < TOLERANCE {
+            return if other.contains(&self.a) { SegmentIntersection::Point(self.a) } else { SegmentIntersection::None };
+        }
+        let dir = dir/len;
+        let project = |p: Coord| (p-self.a).dot(dir);
+
+        let (mut o0, mut o1) = (project(other.a), project(other.b));
+        if o0 > o1 { std::mem::swap(&mut o0, &mut o1); }
+
+        let lo = 0.0_f64.max(o0);
+        let hi = len.min(o1);
+
+        if lo > hi+TOLERANCE {
+            SegmentIntersection::None
+        } else if (hi-lo).abs() < TOLERANCE {
+            SegmentIntersection::Point(self.a+dir*lo)
+        } else {
+            SegmentIntersection::Overlap(Segment{a: self.a+dir*lo, b: self.a+dir*hi, layer: self.layer})
+        }
+    }
+
+    /// Check if two segments intersect, treating both as arcs of great circles
+    /// on the Earth's surface rather than straight lines in the lat/lon plane.
+    /// This avoids the drift `intersection` accumulates on long segments or
+    /// near the poles, where flat Cartesian math stops being a good model.
+    pub fn intersection_spherical(&self, other: &Segment) -> Option<Coord> {
+        if self.layer.is_some() && !self.is_contiguous(other) && self.layer != other.layer {
+            return None;
+        }
+        let p1 = Vec3::from_coord(&self.a);
+        let p2 = Vec3::from_coord(&self.b);
+        let q1 = Vec3::from_coord(&other.a);
+        let q2 = Vec3::from_coord(&other.b);
+
+        let n1 = p1.cross(p2);
+        let n2 = q1.cross(q2);
+        let d = n1.cross(n2);
+
+        let d_norm = d.norm();
+        if d_norm < TOLERANCE {
+            // The great circles are parallel or identical; let the collinear
+            // handling in the planar path deal with it.
+            return None;
+        }
+        let d = d.scale(1.0 / d_norm);
+
+        let mid_self = p1.add(p2);
+        let mid_other = q1.add(q2);
+
+        // Of the two antipodal candidates, the one that can plausibly lie on
+        // both arcs (rather than on their antipodal extensions) is the one
+        // on the same side of the sphere as both arcs' midpoints.
+        let candidate = if d.dot(mid_self) > 0.0 && d.dot(mid_other) > 0.0 {
+            d
+        } else {
+            d.scale(-1.0)
+        };
+
+        if Segment::arc_contains(p1, p2, candidate) && Segment::arc_contains(q1, q2, candidate) {
+            Some(candidate.to_coord())
         } else {
             None
         }
     }
 
+    /// Whether `x`, known to lie on the great circle through `p1` and `p2`,
+    /// falls on the minor arc between them rather than beyond either end.
+    fn arc_contains(p1: Vec3, p2: Vec3, x: Vec3) -> bool {
+        let n = p1.cross(p2);
+        p1.cross(x).dot(n) >= -TOLERANCE && x.cross(p2).dot(n) >= -TOLERANCE
+    }
+
     /// Get the segment's length
     pub fn length(&self) -> f64 {
         self.a.distance(&self.b)
@@ -150,6 +323,91 @@ impl Segment {
         }
     }
 
+    /// Like `distance_from_point`, but projects the point onto the great
+    /// circle through `a` and `b` instead of onto the flat chord between
+    /// them, so the result stays accurate for long segments and high
+    /// latitudes.
+    pub fn distance_from_point_spherical(&self, point: &Point) -> (f64, Coord) {
+        let a = Vec3::from_coord(&self.a);
+        let b = Vec3::from_coord(&self.b);
+        let p = Vec3::from_coord(point);
+
+        let n = a.cross(b);
+        let n_norm = n.norm();
+        if n_norm < TOLERANCE {
+            // a and b are (anti)podal or coincide; there's no unique great circle.
+            let distance_a = point.distance(&self.a);
+            let distance_b = point.distance(&self.b);
+            return if distance_a < distance_b { (distance_a, self.a) } else { (distance_b, self.b) };
+        }
+        let n = n.scale(1.0 / n_norm);
+
+        // Project p onto the great circle's plane, then back onto the sphere.
+        let projected = p.sub(n.scale(n.dot(p)));
+        let projected_norm = projected.norm();
+        if projected_norm < TOLERANCE {
+            let distance_a = point.distance(&self.a);
+            let distance_b = point.distance(&self.b);
+            return if distance_a < distance_b { (distance_a, self.a) } else { (distance_b, self.b) };
+        }
+        let projected = projected.scale(1.0 / projected_norm);
+
+        if Segment::arc_contains(a, b, projected) {
+            let res = projected.to_coord();
+            (res.distance(point), res)
+        } else {
+            let distance_a = point.distance(&self.a);
+            let distance_b = point.distance(&self.b);
+            if distance_a < distance_b {
+                (distance_a, self.a)
+            } else {
+                (distance_b, self.b)
+            }
+        }
+    }
+
+    /// Get the point a fraction `t` (0..=1) of the way from `a` to `b`,
+    /// following the great circle between them rather than the flat chord.
+    /// Used to place markers, snap GPS traces, or subdivide a route.
+    pub fn sample(&self, t: f64) -> Coord {
+        let p0 = Vec3::from_coord(&self.a);
+        let p1 = Vec3::from_coord(&self.b);
+        let omega = p0.dot(p1).clamp(-1.0, 1.0).acos();
+        if omega < TOLERANCE {
+            return self.a;
+        }
+        let sin_omega = omega.sin();
+        let v = p0.scale(((1.0-t)*omega).sin()/sin_omega).add(p1.scale((t*omega).sin()/sin_omega));
+        v.to_coord()
+    }
+
+    /// Inverse of `sample`: the fraction `t` (0..=1) along the great circle
+    /// from `a` to `b` at which `point` lies, or `None` if it isn't on the
+    /// segment.
+    pub fn solve_t_for_point(&self, point: &Coord) -> Option<f64> {
+        if !self.contains(point) {
+            return None;
+        }
+        let p0 = Vec3::from_coord(&self.a);
+        let p1 = Vec3::from_coord(&self.b);
+        let p = Vec3::from_coord(point);
+        let omega = p0.dot(p1).clamp(-1.0, 1.0).acos();
+        if omega < TOLERANCE {
+            return Some(0.0);
+        }
+        let angle_to_point = p0.dot(p).clamp(-1.0, 1.0).acos();
+        Some((angle_to_point/omega).clamp(0.0, 1.0))
+    }
+
+    /// Get the segment's axis-aligned bounding box as (min, max) corners,
+    /// used to index it in spatial acceleration structures.
+    pub fn bounding_box(&self) -> (Coord, Coord) {
+        (
+            Coord{lat: self.a.lat.min(self.b.lat), lon: self.a.lon.min(self.b.lon)},
+            Coord{lat: self.a.lat.max(self.b.lat), lon: self.a.lon.max(self.b.lon)},
+        )
+    }
+
     /// Return a reversed Segment
     pub fn reverse(&self) -> Segment {
         Segment{a: self.b, b: self.a, layer: self.layer}
@@ -180,6 +438,7 @@ impl Segment {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Road{
     pub segments: Vec<Segment>,
     pub name: Option<String>,
@@ -197,6 +456,22 @@ impl Road{
         result
     }
 
+    /// Get the road's axis-aligned bounding box as (min, max) corners,
+    /// used to index it in spatial acceleration structures.
+    pub fn bounding_box(&self) -> (Coord, Coord) {
+        let mut min = self.segments[0].a;
+        let mut max = self.segments[0].a;
+        for segment in &self.segments {
+            for p in [segment.a, segment.b] {
+                min.lat = min.lat.min(p.lat);
+                min.lon = min.lon.min(p.lon);
+                max.lat = max.lat.max(p.lat);
+                max.lon = max.lon.max(p.lon);
+            }
+        }
+        (min, max)
+    }
+
     /// Get the road's total length
     pub fn length(&self) -> f64 {
         let mut total_length = 0.0;
@@ -247,16 +522,55 @@ impl Road{
         distance
     }
 
-    /// Get the intersections with another road
+    /// Get the coordinate `d_km` kilometres from the start of the road,
+    /// or `None` if the road is shorter than that.
+    pub fn point_at_distance(&self, d_km: f64) -> Option<Coord> {
+        if d_km < 0.0 {
+            return None;
+        }
+        let mut remaining = d_km;
+        for segment in &self.segments {
+            let len = segment.length();
+            if remaining <= len+TOLERANCE {
+                let t = if len < TOLERANCE { 0.0 } else { remaining/len };
+                return Some(segment.sample(t.clamp(0.0, 1.0)));
+            }
+            remaining -= len;
+        }
+        None
+    }
+
+    /// Inverse of `point_at_distance`: the distance in kilometres from the
+    /// start of the road to `point`, or `None` if `point` doesn't lie on it.
+    pub fn distance_along(&self, point: &Coord) -> Option<f64> {
+        let mut accumulated = 0.0;
+        for segment in &self.segments {
+            if let Some(t) = segment.solve_t_for_point(point) {
+                return Some(accumulated+segment.length()*t);
+            }
+            accumulated += segment.length();
+        }
+        None
+    }
+
+    /// Get the intersections with another road. A collinear overlap
+    /// contributes both of its endpoints.
     pub fn intersections(&self, with: &Road) -> Vec<Coord> {
         let mut result: Vec<Coord> = Vec::new();
+        let push_unique = |c: Coord, result: &mut Vec<Coord>| {
+            if !result.contains(&c) {
+                result.push(c);
+            }
+        };
         for a_segment in &self.segments {
             for b_segment in &with.segments {
-                if let Some(intersection) = a_segment.intersection(b_segment){
-                    if result.contains(&intersection) {
-                        continue;
-                    }
-                    result.push(intersection)
+                match a_segment.intersection(b_segment) {
+                    SegmentIntersection::Point(p) => push_unique(p, &mut result),
+                    SegmentIntersection::Overlap(segment) => {
+                        push_unique(segment.a, &mut result);
+                        push_unique(segment.b, &mut result);
+                    },
+                    SegmentIntersection::None => {}
                 }
             }
         }
@@ -264,12 +578,14 @@ impl Road{
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BusStop {
     pub position: Coord,
     pub id: String,
     pub name: String,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TrainStation {
     pub name: String,
     pub id: String,
@@ -329,7 +645,7 @@ mod tests {
             b: Point{lat: 3.0, lon: 1.0},
             layer: None
         };
-        assert_eq!(first_segment.intersection(&second_segment), Some(Point{lat: 2.0, lon: 2.0}));
+        assert_eq!(first_segment.intersection(&second_segment), SegmentIntersection::Point(Point{lat: 2.0, lon: 2.0}));
     }
 
     #[test]
@@ -344,7 +660,7 @@ mod tests {
             b: Point{lat: 1.0, lon: 1.0},
             layer: None
         };
-        assert_eq!(first_segment.intersection(&second_segment), Some(first_segment.a));
+        assert_eq!(first_segment.intersection(&second_segment), SegmentIntersection::Point(first_segment.a));
     }
 
     #[test]
@@ -359,7 +675,7 @@ mod tests {
             b: Point{lat: 13.0, lon: 1.0},
             layer: None
         };
-        assert_eq!(first_segment.intersection(&second_segment), None);
+        assert_eq!(first_segment.intersection(&second_segment), SegmentIntersection::None);
     }
 
     #[test]
@@ -446,6 +762,51 @@ mod tests {
         assert_eq!(first_segment.is_contiguous(&first_segment), false);
     }
 
+    #[test]
+    fn segment_intersection_spherical_easy_test() {
+        let first_segment = Segment{
+            a: Point{lat:1.0, lon: 1.0},
+            b: Point{lat: 4.0, lon: 4.0},
+            layer: None
+        };
+        let second_segment = Segment{
+            a: Point{lat:1.0, lon: 3.0},
+            b: Point{lat: 3.0, lon: 1.0},
+            layer: None
+        };
+        let result = first_segment.intersection_spherical(&second_segment).unwrap();
+        assert!((result.lat - 2.0).abs() < 1e-2);
+        assert!((result.lon - 2.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn segment_intersection_spherical_none_test() {
+        let first_segment = Segment{
+            a: Point{lat:1.0, lon: 1.0},
+            b: Point{lat: 4.0, lon: 4.0},
+            layer: None
+        };
+        let second_segment = Segment{
+            a: Point{lat:11.0, lon: 3.0},
+            b: Point{lat: 13.0, lon: 1.0},
+            layer: None
+        };
+        assert_eq!(first_segment.intersection_spherical(&second_segment), None);
+    }
+
+    #[test]
+    fn nearest_point_from_segment_spherical_near_test() {
+        let segment = Segment{
+            a: Point{lat:1.0, lon: 1.0},
+            b: Point{lat: 4.0, lon: 4.0},
+            layer: None
+        };
+        let point = Point{lat: 1.0, lon: 3.0};
+        let (_, projected) = segment.distance_from_point_spherical(&point);
+        assert!((projected.lat - 2.0).abs() < 1e-2);
+        assert!((projected.lon - 2.0).abs() < 1e-2);
+    }
+
     #[test]
     fn segment_intersection_layer_test() {
         let first_segment = Segment{
@@ -463,8 +824,136 @@ mod tests {
             a: Point{lat: -4.0, lon: -2.0},
             layer: Some(-1)
         };
-        assert_eq!(first_segment.intersection(&second_segment), None);
-        assert_eq!(first_segment.intersection(&third_segment), Some(Coord{lat: 1.0, lon: 1.0}));
+        assert_eq!(first_segment.intersection(&second_segment), SegmentIntersection::None);
+        assert_eq!(first_segment.intersection(&third_segment), SegmentIntersection::Point(Coord{lat: 1.0, lon: 1.0}));
+    }
+
+    #[test]
+    fn collinear_overlap_test() {
+        let first_segment = Segment{
+            a: Point{lat:0.0, lon: 0.0},
+            b: Point{lat: 4.0, lon: 0.0},
+            layer: None
+        };
+        let second_segment = Segment{
+            a: Point{lat:2.0, lon: 0.0},
+            b: Point{lat: 6.0, lon: 0.0},
+            layer: None
+        };
+        assert_eq!(
+            first_segment.intersection(&second_segment),
+            SegmentIntersection::Overlap(Segment{
+                a: Point{lat:2.0, lon: 0.0},
+                b: Point{lat:4.0, lon: 0.0},
+                layer: None
+            })
+        );
+    }
+
+    #[test]
+    fn collinear_touching_at_point_test() {
+        let first_segment = Segment{
+            a: Point{lat:0.0, lon: 0.0},
+            b: Point{lat: 4.0, lon: 0.0},
+            layer: None
+        };
+        let second_segment = Segment{
+            a: Point{lat:4.0, lon: 0.0},
+            b: Point{lat: 8.0, lon: 0.0},
+            layer: None
+        };
+        assert_eq!(first_segment.intersection(&second_segment), SegmentIntersection::Point(Point{lat:4.0, lon: 0.0}));
+    }
+
+    #[test]
+    fn collinear_no_overlap_test() {
+        let first_segment = Segment{
+            a: Point{lat:0.0, lon: 0.0},
+            b: Point{lat: 4.0, lon: 0.0},
+            layer: None
+        };
+        let second_segment = Segment{
+            a: Point{lat:5.0, lon: 0.0},
+            b: Point{lat: 8.0, lon: 0.0},
+            layer: None
+        };
+        assert_eq!(first_segment.intersection(&second_segment), SegmentIntersection::None);
+    }
+
+    #[test]
+    fn segment_sample_test() {
+        let segment = Segment{
+            a: Point{lat:0.0, lon: 0.0},
+            b: Point{lat: 0.0, lon: 4.0},
+            layer: None
+        };
+        let midpoint = segment.sample(0.5);
+        assert!((midpoint.lat-0.0).abs() < 1e-6);
+        assert!((midpoint.lon-2.0).abs() < 1e-6);
+        assert_eq!(segment.sample(0.0), segment.a);
+    }
+
+    #[test]
+    fn segment_solve_t_for_point_test() {
+        let segment = Segment{
+            a: Point{lat:0.0, lon: 0.0},
+            b: Point{lat: 0.0, lon: 4.0},
+            layer: None
+        };
+        let point = Point{lat: 0.0, lon: 1.0};
+        let t = segment.solve_t_for_point(&point).unwrap();
+        assert!((t-0.25).abs() < 1e-6);
+        assert_eq!(segment.solve_t_for_point(&Point{lat: 10.0, lon: 10.0}), None);
+    }
+
+    #[test]
+    fn road_point_at_distance_test() {
+        let road = Road{
+            name: None,
+            segments: vec![
+                Segment{
+                    a: Point{lat:0.0, lon: 0.0},
+                    b: Point{lat: 0.0, lon: 1.0},
+                    layer: None
+                },
+                Segment{
+                    a: Point{lat:0.0, lon: 1.0},
+                    b: Point{lat: 0.0, lon: 2.0},
+                    layer: None
+                }
+            ],
+            forbidden_to_pedestrians: false,
+            forbidden_to_bikes: false
+        };
+        let half_distance = road.length()/2.0;
+        let point = road.point_at_distance(half_distance).unwrap();
+        assert!((point.lat-0.0).abs() < 1e-6);
+        assert!((point.lon-1.0).abs() < 1e-2);
+        assert_eq!(road.point_at_distance(road.length()*2.0), None);
+    }
+
+    #[test]
+    fn road_distance_along_test() {
+        let road = Road{
+            name: None,
+            segments: vec![
+                Segment{
+                    a: Point{lat:0.0, lon: 0.0},
+                    b: Point{lat: 0.0, lon: 1.0},
+                    layer: None
+                },
+                Segment{
+                    a: Point{lat:0.0, lon: 1.0},
+                    b: Point{lat: 0.0, lon: 2.0},
+                    layer: None
+                }
+            ],
+            forbidden_to_pedestrians: false,
+            forbidden_to_bikes: false
+        };
+        let point = Point{lat: 0.0, lon: 1.5};
+        let distance = road.distance_along(&point).unwrap();
+        assert!((distance-road.length()*0.75).abs() < 0.1);
     }
 
 }
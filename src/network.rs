@@ -0,0 +1,223 @@
+use rstar::{RTree, RTreeObject, AABB, PointDistance};
+
+use crate::{Coord, Road, SegmentIntersection};
+
+/// Lightweight handle stored in the R-tree: just a road's bounding box and
+/// its index into `RoadNetwork::roads`, so the tree doesn't need to own or
+/// clone the (potentially large) `Road` itself.
+struct RoadEnvelope {
+    index: usize,
+    envelope: AABB<[f64; 2]>,
+}
+
+impl RTreeObject for RoadEnvelope {
+    type Envelope = AABB<[f64; 2]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        self.envelope
+    }
+}
+
+impl PointDistance for RoadEnvelope {
+    fn distance_2(&self, point: &[f64; 2]) -> f64 {
+        self.envelope.distance_2(point)
+    }
+}
+
+fn road_envelope(road: &Road) -> AABB<[f64; 2]> {
+    let (min, max) = road.bounding_box();
+    AABB::from_corners([min.lon, min.lat], [max.lon, max.lat])
+}
+
+/// A collection of roads accelerated with an R-tree over their bounding
+/// boxes, so nearest-road and intersection queries on a city-sized map
+/// don't degrade to a linear scan over every road.
+pub struct RoadNetwork {
+    roads: Vec<Road>,
+    tree: RTree<RoadEnvelope>,
+}
+
+impl RoadNetwork {
+    pub fn new(roads: Vec<Road>) -> RoadNetwork {
+        let entries = roads.iter().enumerate().map(|(index, road)| RoadEnvelope {
+            index,
+            envelope: road_envelope(road),
+        }).collect();
+        let tree = RTree::bulk_load(entries);
+        RoadNetwork { roads, tree }
+    }
+
+    pub fn roads(&self) -> &[Road] {
+        &self.roads
+    }
+
+    /// Get the road whose nearest point is closest to `point`.
+    ///
+    /// `nearest_neighbor_iter` only orders candidates by their bounding
+    /// box's lower-bound distance, not true point-to-road distance, so a
+    /// fixed-size cutoff can miss the real answer. Instead this keeps
+    /// pulling candidates while their bbox lower bound is still less than
+    /// the best true distance found so far; once it isn't, no later
+    /// candidate (they only get farther) can beat the current best.
+    pub fn nearest_road(&self, point: &Coord) -> Option<&Road> {
+        // An approximate conversion (1 degree ~ 111 km), applied here to compare the bbox's degree-space lower bound
+        // against the best true distance found so far, in the same units.
+        const KM_PER_DEGREE: f64 = 111.0;
+        let query = [point.lon, point.lat];
+        let mut best: Option<(f64, &Road)> = None;
+        for entry in self.tree.nearest_neighbor_iter(&query) {
+            if let Some((best_distance, _)) = best {
+                let bbox_lower_bound_km = entry.envelope.distance_2(&query).sqrt() * KM_PER_DEGREE;
+                if bbox_lower_bound_km > best_distance {
+                    break;
+                }
+            }
+            let road = &self.roads[entry.index];
+            let distance = road.distance_from_nearest_point(point).0;
+            if best.map_or(true, |(best_distance, _)| distance < best_distance) {
+                best = Some((distance, road));
+            }
+        }
+        best.map(|(_, road)| road)
+    }
+
+    /// Get every road passing within `radius_km` of `point`.
+    pub fn roads_within(&self, point: &Coord, radius_km: f64) -> Vec<&Road> {
+        // Bounding box of the spherical cap of radius `radius_km` around
+        // `point`: a degree of longitude shrinks with latitude, so the
+        // longitude half-width grows away from the equator and spans every
+        // longitude once the cap reaches a pole. The box search therefore
+        // never discards a candidate that the haversine check below would
+        // have kept (longitudes are not wrapped across +/-180 degrees).
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+        let angular_radius = radius_km / EARTH_RADIUS_KM;
+        let lat = point.lat.to_radians();
+        let radius_lat_deg = angular_radius.to_degrees();
+        let radius_lon_deg = if lat.abs() + angular_radius < std::f64::consts::FRAC_PI_2 {
+            (angular_radius.sin() / lat.cos()).asin().to_degrees()
+        } else {
+            180.0
+        };
+        let envelope = AABB::from_corners(
+            [point.lon - radius_lon_deg, point.lat - radius_lat_deg],
+            [point.lon + radius_lon_deg, point.lat + radius_lat_deg],
+        );
+        self.tree
+            .locate_in_envelope_intersecting(&envelope)
+            .map(|entry| &self.roads[entry.index])
+            .filter(|road| road.distance_from_nearest_point(point).0 <= radius_km)
+            .collect()
+    }
+
+    /// All intersections between distinct roads in the network. Road pairs
+    /// whose bounding boxes don't overlap are skipped outright, and within
+    /// an overlapping pair only segments whose own bounding boxes overlap
+    /// are actually tested.
+    pub fn all_intersections(&self) -> Vec<Coord> {
+        let mut result: Vec<Coord> = Vec::new();
+        for (i, road_i) in self.roads.iter().enumerate() {
+            for entry in self.tree.locate_in_envelope_intersecting(&road_envelope(road_i)) {
+                if entry.index <= i {
+                    continue;
+                }
+                let road_j = &self.roads[entry.index];
+                for segment_a in &road_i.segments {
+                    let (a_min, a_max) = segment_a.bounding_box();
+                    for segment_b in &road_j.segments {
+                        let (b_min, b_max) = segment_b.bounding_box();
+                        if a_max.lon < b_min.lon || b_max.lon < a_min.lon
+                            || a_max.lat < b_min.lat || b_max.lat < a_min.lat {
+                            continue;
+                        }
+                        match segment_a.intersection(segment_b) {
+                            SegmentIntersection::Point(p) => {
+                                if !result.contains(&p) {
+                                    result.push(p);
+                                }
+                            },
+                            SegmentIntersection::Overlap(segment) => {
+                                if !result.contains(&segment.a) {
+                                    result.push(segment.a);
+                                }
+                                if !result.contains(&segment.b) {
+                                    result.push(segment.b);
+                                }
+                            },
+                            SegmentIntersection::None => {}
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Segment;
+
+    fn road(name: &str, a: Coord, b: Coord) -> Road {
+        Road{
+            name: Some(name.to_string()),
+            segments: vec![Segment{a, b, layer: None}],
+            forbidden_to_pedestrians: false,
+            forbidden_to_bikes: false,
+        }
+    }
+
+    #[test]
+    fn nearest_road_picks_the_closest_of_several_candidates_test() {
+        let network = RoadNetwork::new(vec![
+            road("far", Coord{lat: 10.0, lon: 10.0}, Coord{lat: 10.0, lon: 11.0}),
+            road("near", Coord{lat: 0.0, lon: 0.0}, Coord{lat: 0.0, lon: 1.0}),
+            road("middle", Coord{lat: 5.0, lon: 5.0}, Coord{lat: 5.0, lon: 6.0}),
+        ]);
+        let nearest = network.nearest_road(&Coord{lat: 0.1, lon: 0.5}).unwrap();
+        assert_eq!(nearest.name.as_deref(), Some("near"));
+    }
+
+    #[test]
+    fn roads_within_excludes_roads_outside_the_radius_test() {
+        let network = RoadNetwork::new(vec![
+            road("close", Coord{lat: 0.0, lon: 0.0}, Coord{lat: 0.0, lon: 0.01}),
+            road("far", Coord{lat: 20.0, lon: 20.0}, Coord{lat: 20.0, lon: 21.0}),
+        ]);
+        let within = network.roads_within(&Coord{lat: 0.0, lon: 0.0}, 5.0);
+        assert_eq!(within.len(), 1);
+        assert_eq!(within[0].name.as_deref(), Some("close"));
+    }
+
+    #[test]
+    fn roads_within_respects_the_radius_boundary_test() {
+        let network = RoadNetwork::new(vec![
+            road("edge", Coord{lat: 0.0, lon: 0.0}, Coord{lat: 0.0, lon: 0.001}),
+        ]);
+        let point = Coord{lat: 0.0, lon: 0.1};
+        let exact_distance = network.roads()[0].distance_from_nearest_point(&point).0;
+
+        assert_eq!(network.roads_within(&point, exact_distance + 0.01).len(), 1);
+        assert_eq!(network.roads_within(&point, exact_distance - 0.01).len(), 0);
+    }
+
+    #[test]
+    fn all_intersections_finds_crossings_between_distinct_roads_test() {
+        let network = RoadNetwork::new(vec![
+            road("horizontal", Coord{lat: 0.0, lon: 0.0}, Coord{lat: 0.0, lon: 2.0}),
+            road("vertical", Coord{lat: -1.0, lon: 1.0}, Coord{lat: 1.0, lon: 1.0}),
+        ]);
+        let intersections = network.all_intersections();
+        assert_eq!(intersections.len(), 1);
+        assert_eq!(intersections[0], Coord{lat: 0.0, lon: 1.0});
+    }
+
+    #[test]
+    fn all_intersections_is_empty_for_non_crossing_roads_test() {
+        let network = RoadNetwork::new(vec![
+            road("one", Coord{lat: 0.0, lon: 0.0}, Coord{lat: 0.0, lon: 1.0}),
+            road("two", Coord{lat: 5.0, lon: 5.0}, Coord{lat: 5.0, lon: 6.0}),
+        ]);
+        assert!(network.all_intersections().is_empty());
+    }
+}
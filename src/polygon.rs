@@ -0,0 +1,220 @@
+//! A closed-area primitive for modelling transit zones, fare boundaries, or
+//! service areas, complementing `Road`'s open polyline.
+
+use crate::{Coord, Road, Segment, SegmentIntersection, TOLERANCE};
+
+/// A closed area described by an exterior ring and zero or more holes, each
+/// a sequence of coordinates implicitly closed from the last point back to
+/// the first.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Polygon {
+    pub exterior: Vec<Coord>,
+    pub holes: Vec<Vec<Coord>>,
+}
+
+impl Polygon {
+    /// Whether `point` falls inside the polygon, via ray casting: a ray
+    /// cast eastward from `point` crosses the boundary an odd number of
+    /// times iff the point is inside. A point exactly on an exterior edge
+    /// counts as inside, and a point inside a hole, or exactly on a hole's
+    /// edge, counts as outside.
+    pub fn contains(&self, point: &Coord) -> bool {
+        if !Self::ring_contains(&self.exterior, point) {
+            return false;
+        }
+        !self.holes.iter().any(|hole| Self::ring_contains(hole, point))
+    }
+
+    fn ring_contains(ring: &[Coord], point: &Coord) -> bool {
+        let n = ring.len();
+        if n < 3 {
+            return false;
+        }
+        for i in 0..n {
+            let edge = Segment{a: ring[i], b: ring[(i+1)%n], layer: None};
+            if edge.contains(point) {
+                return true;
+            }
+        }
+        let mut inside = false;
+        for i in 0..n {
+            let a = ring[i];
+            let b = ring[(i+1)%n];
+            if (a.lat > point.lat) != (b.lat > point.lat) {
+                let lon_at_point_lat = a.lon+(point.lat-a.lat)/(b.lat-a.lat)*(b.lon-a.lon);
+                if point.lon < lon_at_point_lat {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+
+    /// Get the polygon's area in square kilometres, using the spherical
+    /// excess ("spherical shoelace") formula so results stay accurate at
+    /// map scale, minus the area of any holes.
+    pub fn area_km2(&self) -> f64 {
+        let mut area = Self::ring_area_km2(&self.exterior);
+        for hole in &self.holes {
+            area -= Self::ring_area_km2(hole);
+        }
+        area.abs()
+    }
+
+    fn ring_area_km2(ring: &[Coord]) -> f64 {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+        let n = ring.len();
+        if n < 3 {
+            return 0.0;
+        }
+        let mut total = 0.0;
+        for i in 0..n {
+            let p1 = ring[i];
+            let p2 = ring[(i+1)%n];
+            let lambda1 = p1.lon.to_radians();
+            let lambda2 = p2.lon.to_radians();
+            let phi1 = p1.lat.to_radians();
+            let phi2 = p2.lat.to_radians();
+            total += (lambda2-lambda1)*(2.0+phi1.sin()+phi2.sin());
+        }
+        (total*EARTH_RADIUS_KM*EARTH_RADIUS_KM/2.0).abs()
+    }
+
+    /// Get the centroid of the exterior ring's vertices (their mean
+    /// position); this is not area-weighted, and holes are ignored.
+    pub fn centroid(&self) -> Coord {
+        let n = self.exterior.len() as f64;
+        let sum = self.exterior.iter().fold(Coord{lat: 0.0, lon: 0.0}, |acc, p| acc+*p);
+        sum/n
+    }
+
+    /// Get the portions of `road` that lie inside the polygon, as separate
+    /// `Road`s split at every boundary crossing.
+    pub fn clip_road(&self, road: &Road) -> Vec<Road> {
+        let mut rings: Vec<&Vec<Coord>> = vec![&self.exterior];
+        rings.extend(self.holes.iter());
+
+        let mut result: Vec<Road> = Vec::new();
+        let mut current: Vec<Segment> = Vec::new();
+
+        for segment in &road.segments {
+            // `intersection` finds crossings by flat line-line solve, so the
+            // fraction along `segment` must be recovered the same flat way
+            // rather than via `solve_t_for_point`'s great-circle angle ratio
+            // - otherwise `lerp` below reconstructs a different point than
+            // the one the crossing was found at.
+            let dir = segment.b-segment.a;
+            let lerp = |t: f64| segment.a+dir*t;
+            let flat_t = |p: &Coord| if dir.lon.abs() >= dir.lat.abs() {
+                (p.lon-segment.a.lon)/dir.lon
+            } else {
+                (p.lat-segment.a.lat)/dir.lat
+            };
+
+            let mut ts: Vec<f64> = vec![0.0, 1.0];
+            for ring in &rings {
+                let n = ring.len();
+                for i in 0..n {
+                    let edge = Segment{a: ring[i], b: ring[(i+1)%n], layer: None};
+                    if let SegmentIntersection::Point(p) = segment.intersection(&edge) {
+                        ts.push(flat_t(&p));
+                    }
+                }
+            }
+            ts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            ts.dedup_by(|a, b| (*a-*b).abs() < TOLERANCE);
+
+            for pair in ts.windows(2) {
+                let midpoint = lerp((pair[0]+pair[1])/2.0);
+                if self.contains(&midpoint) {
+                    current.push(Segment{a: lerp(pair[0]), b: lerp(pair[1]), layer: segment.layer});
+                } else if !current.is_empty() {
+                    result.push(Self::finish_road(&mut current, road));
+                }
+            }
+        }
+        if !current.is_empty() {
+            result.push(Self::finish_road(&mut current, road));
+        }
+        result
+    }
+
+    fn finish_road(segments: &mut Vec<Segment>, template: &Road) -> Road {
+        Road{
+            segments: std::mem::take(segments),
+            name: template.name.clone(),
+            forbidden_to_pedestrians: template.forbidden_to_pedestrians,
+            forbidden_to_bikes: template.forbidden_to_bikes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_square() -> Polygon {
+        Polygon{
+            exterior: vec![
+                Coord{lat: 0.0, lon: 0.0},
+                Coord{lat: 0.0, lon: 1.0},
+                Coord{lat: 1.0, lon: 1.0},
+                Coord{lat: 1.0, lon: 0.0},
+            ],
+            holes: vec![],
+        }
+    }
+
+    #[test]
+    fn contains_inside_and_outside_test() {
+        let square = unit_square();
+        assert!(square.contains(&Coord{lat: 0.5, lon: 0.5}));
+        assert!(!square.contains(&Coord{lat: 2.0, lon: 2.0}));
+        assert!(square.contains(&Coord{lat: 0.0, lon: 0.5}));
+    }
+
+    #[test]
+    fn contains_excludes_holes_test() {
+        let mut square = unit_square();
+        square.holes.push(vec![
+            Coord{lat: 0.25, lon: 0.25},
+            Coord{lat: 0.25, lon: 0.75},
+            Coord{lat: 0.75, lon: 0.75},
+            Coord{lat: 0.75, lon: 0.25},
+        ]);
+        assert!(!square.contains(&Coord{lat: 0.5, lon: 0.5}));
+        assert!(square.contains(&Coord{lat: 0.1, lon: 0.1}));
+    }
+
+    #[test]
+    fn area_km2_roughly_matches_flat_estimate_test() {
+        let square = unit_square();
+        // A degree is about 111 km on a side near the equator.
+        let expected = 111.0*111.0;
+        assert!((square.area_km2()-expected).abs()/expected < 0.05);
+    }
+
+    #[test]
+    fn centroid_test() {
+        let square = unit_square();
+        let centroid = square.centroid();
+        assert!((centroid.lat-0.5).abs() < 1e-9);
+        assert!((centroid.lon-0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn clip_road_keeps_only_the_portion_inside_test() {
+        let square = unit_square();
+        let road = Road{
+            name: None,
+            segments: vec![Segment{a: Coord{lat: 0.5, lon: -1.0}, b: Coord{lat: 0.5, lon: 2.0}, layer: None}],
+            forbidden_to_pedestrians: false,
+            forbidden_to_bikes: false,
+        };
+        let clipped = square.clip_road(&road);
+        assert_eq!(clipped.len(), 1);
+        assert_eq!(clipped[0].segments.len(), 1);
+        assert!((clipped[0].segments[0].a.lon-0.0).abs() < TOLERANCE);
+        assert!((clipped[0].segments[0].b.lon-1.0).abs() < TOLERANCE);
+    }
+}
@@ -0,0 +1,317 @@
+//! A routable graph built on top of a `RoadNetwork`: roads are split into
+//! edges at every shared endpoint or computed intersection, and `Graph`
+//! answers shortest-path queries over the result with A*.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::{Coord, Road, RoadNetwork, SegmentIntersection, TOLERANCE};
+
+/// The kind of traveller a route is being computed for; prunes edges the
+/// traveller isn't allowed to use.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TravelMode {
+    Pedestrian,
+    Bike,
+    Car,
+}
+
+struct Edge {
+    to: usize,
+    length_km: f64,
+    penalty: f64,
+    forbidden_to_pedestrians: bool,
+    forbidden_to_bikes: bool,
+}
+
+impl Edge {
+    fn is_forbidden(&self, mode: TravelMode) -> bool {
+        match mode {
+            TravelMode::Pedestrian => self.forbidden_to_pedestrians,
+            TravelMode::Bike => self.forbidden_to_bikes,
+            TravelMode::Car => false,
+        }
+    }
+
+    fn weight(&self) -> f64 {
+        self.length_km + self.penalty
+    }
+}
+
+/// A node-and-edge graph over a `RoadNetwork`'s roads, ready for
+/// shortest-path queries.
+pub struct Graph<'a> {
+    network: &'a RoadNetwork,
+    nodes: Vec<Coord>,
+    edges: Vec<Vec<Edge>>,
+}
+
+impl<'a> Graph<'a> {
+    /// Build a graph where every edge costs exactly its length.
+    pub fn from_network(network: &'a RoadNetwork) -> Graph<'a> {
+        Self::from_network_with_penalty(network, |_| 0.0)
+    }
+
+    /// Build a graph where `penalty_for` adds an extra, road-specific cost
+    /// on top of each edge's length - useful to discourage (rather than
+    /// forbid) restricted or low-priority roads.
+    pub fn from_network_with_penalty<F>(network: &'a RoadNetwork, penalty_for: F) -> Graph<'a>
+    where F: Fn(&Road) -> f64 {
+        let mut nodes: Vec<Coord> = Vec::new();
+        let mut edges: Vec<Vec<Edge>> = Vec::new();
+
+        // Computed once via the network's R-tree rather than by comparing
+        // every segment against every other, so splitting roads at their
+        // intersections stays cheap on a city-scale network. This only
+        // covers crossings between distinct roads, so a road crossing
+        // itself is handled separately, per road, below.
+        let intersections = network.all_intersections();
+
+        for road in network.roads() {
+            let penalty = penalty_for(road);
+            let self_intersections = Self::self_intersections(road);
+            for segment in &road.segments {
+                let mut split_points = vec![segment.a, segment.b];
+                for point in intersections.iter().chain(self_intersections.iter()) {
+                    if segment.contains(point) {
+                        split_points.push(*point);
+                    }
+                }
+
+                split_points.sort_by(|x, y| {
+                    let tx = segment.solve_t_for_point(x).unwrap_or(0.0);
+                    let ty = segment.solve_t_for_point(y).unwrap_or(0.0);
+                    tx.partial_cmp(&ty).unwrap_or(Ordering::Equal)
+                });
+                split_points.dedup_by(|x, y| (*x-*y).norm() < TOLERANCE);
+
+                for pair in split_points.windows(2) {
+                    let a_index = Self::node_index(&mut nodes, pair[0]);
+                    let b_index = Self::node_index(&mut nodes, pair[1]);
+                    let length_km = pair[0].distance(&pair[1]);
+                    Self::ensure_node(&mut edges, a_index.max(b_index));
+                    edges[a_index].push(Edge{
+                        to: b_index, length_km, penalty,
+                        forbidden_to_pedestrians: road.forbidden_to_pedestrians,
+                        forbidden_to_bikes: road.forbidden_to_bikes,
+                    });
+                    edges[b_index].push(Edge{
+                        to: a_index, length_km, penalty,
+                        forbidden_to_pedestrians: road.forbidden_to_pedestrians,
+                        forbidden_to_bikes: road.forbidden_to_bikes,
+                    });
+                }
+            }
+        }
+
+        Graph{network, nodes, edges}
+    }
+
+    /// Points where a road crosses itself - a single road's segments are
+    /// few enough that comparing them all pairwise is cheap, unlike
+    /// comparing every segment in the network against every other.
+    fn self_intersections(road: &Road) -> Vec<Coord> {
+        let mut result = Vec::new();
+        for (i, segment_a) in road.segments.iter().enumerate() {
+            for segment_b in &road.segments[i+1..] {
+                match segment_a.intersection(segment_b) {
+                    SegmentIntersection::Point(p) => result.push(p),
+                    SegmentIntersection::Overlap(overlap) => {
+                        result.push(overlap.a);
+                        result.push(overlap.b);
+                    },
+                    SegmentIntersection::None => {}
+                }
+            }
+        }
+        result
+    }
+
+    fn node_index(nodes: &mut Vec<Coord>, point: Coord) -> usize {
+        if let Some(index) = nodes.iter().position(|n| (*n-point).norm() < TOLERANCE) {
+            index
+        } else {
+            nodes.push(point);
+            nodes.len()-1
+        }
+    }
+
+    fn ensure_node(edges: &mut Vec<Vec<Edge>>, index: usize) {
+        while edges.len() <= index {
+            edges.push(Vec::new());
+        }
+    }
+
+    fn nearest_node(&self, point: &Coord) -> Option<usize> {
+        self.nodes.iter().enumerate()
+            .min_by(|(_, a), (_, b)| a.distance(point).partial_cmp(&b.distance(point)).unwrap())
+            .map(|(index, _)| index)
+    }
+
+    /// Find the shortest path from `from` to `to` for the given travel
+    /// mode, snapping both endpoints to the nearest point on the network's
+    /// roads first. Returns the path as an ordered list of coordinates
+    /// together with its total length in kilometres.
+    pub fn shortest_path(&self, from: &Coord, to: &Coord, mode: TravelMode) -> Option<(Vec<Coord>, f64)> {
+        let start_point = self.network.nearest_road(from)?.distance_from_nearest_point(from).1;
+        let goal_point = self.network.nearest_road(to)?.distance_from_nearest_point(to).1;
+        let start = self.nearest_node(&start_point)?;
+        let goal = self.nearest_node(&goal_point)?;
+        self.astar(start, goal, mode)
+    }
+
+    fn astar(&self, start: usize, goal: usize, mode: TravelMode) -> Option<(Vec<Coord>, f64)> {
+        let mut dist = vec![f64::INFINITY; self.nodes.len()];
+        let mut prev: Vec<Option<usize>> = vec![None; self.nodes.len()];
+        let mut heap = BinaryHeap::new();
+
+        dist[start] = 0.0;
+        heap.push(State{priority: self.nodes[start].distance(&self.nodes[goal]), cost: 0.0, node: start});
+
+        while let Some(State{cost, node, ..}) = heap.pop() {
+            if node == goal {
+                // `dist` includes penalties; the reported length is the
+                // path's geometric length alone.
+                let path = self.reconstruct_path(&prev, start, goal);
+                let length_km: f64 = path.windows(2).map(|w| w[0].distance(&w[1])).sum();
+                return Some((path, length_km));
+            }
+            if cost > dist[node] {
+                continue;
+            }
+            for edge in &self.edges[node] {
+                if edge.is_forbidden(mode) {
+                    continue;
+                }
+                let next_cost = cost+edge.weight();
+                if next_cost < dist[edge.to] {
+                    dist[edge.to] = next_cost;
+                    prev[edge.to] = Some(node);
+                    let priority = next_cost+self.nodes[edge.to].distance(&self.nodes[goal]);
+                    heap.push(State{priority, cost: next_cost, node: edge.to});
+                }
+            }
+        }
+        None
+    }
+
+    fn reconstruct_path(&self, prev: &[Option<usize>], start: usize, goal: usize) -> Vec<Coord> {
+        let mut path = vec![goal];
+        let mut current = goal;
+        while current != start {
+            current = prev[current].expect("every visited node but start has a predecessor");
+            path.push(current);
+        }
+        path.reverse();
+        path.into_iter().map(|index| self.nodes[index]).collect()
+    }
+}
+
+/// A* frontier entry; ordered by `priority` (cost-so-far plus the haversine
+/// heuristic to the goal) so `BinaryHeap`, a max-heap, pops the lowest
+/// priority first.
+struct State {
+    priority: f64,
+    cost: f64,
+    node: usize,
+}
+
+impl PartialEq for State {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl Eq for State {}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.partial_cmp(&self.priority).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Segment;
+
+    fn grid_network() -> RoadNetwork {
+        let horizontal = Road{
+            name: Some("horizontal".to_string()),
+            segments: vec![Segment{a: Coord{lat: 0.0, lon: 0.0}, b: Coord{lat: 0.0, lon: 2.0}, layer: None}],
+            forbidden_to_pedestrians: false,
+            forbidden_to_bikes: false,
+        };
+        let vertical = Road{
+            name: Some("vertical".to_string()),
+            segments: vec![Segment{a: Coord{lat: -1.0, lon: 1.0}, b: Coord{lat: 1.0, lon: 1.0}, layer: None}],
+            forbidden_to_pedestrians: true,
+            forbidden_to_bikes: false,
+        };
+        RoadNetwork::new(vec![horizontal, vertical])
+    }
+
+    #[test]
+    fn shortest_path_along_two_crossing_roads_test() {
+        let network = grid_network();
+        let graph = Graph::from_network(&network);
+
+        let (path, length) = graph.shortest_path(
+            &Coord{lat: 0.0, lon: 0.0},
+            &Coord{lat: 1.0, lon: 1.0},
+            TravelMode::Car,
+        ).unwrap();
+
+        assert_eq!(path.first(), Some(&Coord{lat: 0.0, lon: 0.0}));
+        assert_eq!(path.last(), Some(&Coord{lat: 1.0, lon: 1.0}));
+        assert!(length > 0.0);
+    }
+
+    #[test]
+    fn shortest_path_respects_forbidden_mode_test() {
+        let network = grid_network();
+        let graph = Graph::from_network(&network);
+
+        // The vertical road is forbidden to pedestrians, so there's no way
+        // to reach a point that's only reachable through it.
+        assert!(graph.shortest_path(
+            &Coord{lat: 0.0, lon: 0.0},
+            &Coord{lat: 1.0, lon: 1.0},
+            TravelMode::Pedestrian,
+        ).is_none());
+    }
+
+    #[test]
+    fn shortest_path_uses_a_road_crossing_itself_test() {
+        // A single road made of two segments that cross each other at
+        // (1,1) without sharing an endpoint - the graph must still split
+        // it there to connect (0,0) to (0,2) through the crossing.
+        let figure_eight = Road{
+            name: Some("figure-eight".to_string()),
+            segments: vec![
+                Segment{a: Coord{lat: 0.0, lon: 0.0}, b: Coord{lat: 2.0, lon: 2.0}, layer: None},
+                Segment{a: Coord{lat: 0.0, lon: 2.0}, b: Coord{lat: 2.0, lon: 0.0}, layer: None},
+            ],
+            forbidden_to_pedestrians: false,
+            forbidden_to_bikes: false,
+        };
+        let network = RoadNetwork::new(vec![figure_eight]);
+        let graph = Graph::from_network(&network);
+
+        let (path, _) = graph.shortest_path(
+            &Coord{lat: 0.0, lon: 0.0},
+            &Coord{lat: 0.0, lon: 2.0},
+            TravelMode::Car,
+        ).unwrap();
+
+        assert_eq!(path.first(), Some(&Coord{lat: 0.0, lon: 0.0}));
+        assert_eq!(path.last(), Some(&Coord{lat: 0.0, lon: 2.0}));
+        assert!(path.contains(&Coord{lat: 1.0, lon: 1.0}));
+    }
+}